@@ -3,7 +3,7 @@ use std::net::TcpListener;
 
 fn main() {
     let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
-    let mut db = rustore::Database::open("example.db").unwrap();
+    let mut db: rustore::Database<String> = rustore::Database::open("example.db").unwrap();
 
     println!("Server listening on port 8080");
     for stream in listener.incoming() {
@@ -27,26 +27,46 @@ fn main() {
                                 let result = db.get(key);
                                 match result {
                                     Ok(value) => {
-                                        stream.write(value.as_bytes()).unwrap();
+                                        stream.write_all(value.as_bytes()).unwrap();
                                     }
                                     Err(_) => {
-                                        stream.write("Key not found \n".as_bytes()).unwrap();
+                                        stream.write_all("Key not found \n".as_bytes()).unwrap();
                                     }
                                 }
                             }
                             "put" => {
                                 let key = tokens[1];
                                 let value = tokens[2];
-                                db.put(key, value).unwrap();
-                                stream.write("OK".as_bytes()).unwrap();
+                                db.put(key, &value.to_string()).unwrap();
+                                stream.write_all("OK".as_bytes()).unwrap();
                             }
                             "delete" => {
                                 let key = tokens[1];
                                 db.delete(key).unwrap();
-                                stream.write("OK".as_bytes()).unwrap();
+                                stream.write_all("OK".as_bytes()).unwrap();
+                            }
+                            "use" => {
+                                let name = tokens[1];
+                                db.use_keyspace(name);
+                                stream.write_all("OK".as_bytes()).unwrap();
+                            }
+                            "keyspaces" => {
+                                let names = db.list_keyspaces().join(", ");
+                                stream.write_all(names.as_bytes()).unwrap();
+                            }
+                            "upgrade" => {
+                                let path = tokens[1];
+                                match rustore::Database::<String>::upgrade(path) {
+                                    Ok(()) => {
+                                        stream.write_all("OK".as_bytes()).unwrap();
+                                    }
+                                    Err(_) => {
+                                        stream.write_all("Upgrade failed".as_bytes()).unwrap();
+                                    }
+                                }
                             }
                             _ => {
-                                stream.write("Invalid command".as_bytes()).unwrap();
+                                stream.write_all("Invalid command".as_bytes()).unwrap();
                             }
                         }
 
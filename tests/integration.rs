@@ -2,9 +2,9 @@ use rustore::*;
 use tempfile::NamedTempFile;
 
 // Helper function to create a temporary database
-fn create_temp_db() -> (Database, NamedTempFile) {
+fn create_temp_db() -> (Database<String>, NamedTempFile) {
     let temp_file = NamedTempFile::new().unwrap();
-    let db = Database::open(temp_file.path().to_str().unwrap()).unwrap();
+    let db: Database<String> = Database::open(temp_file.path().to_str().unwrap()).unwrap();
     (db, temp_file)
 }
 
@@ -13,12 +13,12 @@ fn test_put_and_get() {
     let (mut db, _temp_file) = create_temp_db();
 
     // Test putting and getting a single value
-    db.put("key1", "value1").unwrap();
+    db.put("key1", &"value1".to_string()).unwrap();
     assert_eq!(db.get("key1").unwrap(), "value1");
 
     // Test putting and getting multiple values
-    db.put("key2", "value2").unwrap();
-    db.put("key3", "value3").unwrap();
+    db.put("key2", &"value2".to_string()).unwrap();
+    db.put("key3", &"value3".to_string()).unwrap();
 
     assert_eq!(db.get("key2").unwrap(), "value2");
     assert_eq!(db.get("key3").unwrap(), "value3");
@@ -29,8 +29,8 @@ fn test_get_non_existent_key() {
     let (db, _temp_file) = create_temp_db();
 
     match db.get("nonexistent") {
-        Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
-        Ok(_) => panic!("Expected error for non-existent key"),
+        Err(Error::NotFound) => (),
+        _ => panic!("Expected NotFound error"),
     }
 }
 
@@ -39,15 +39,15 @@ fn test_delete() {
     let (mut db, _temp_file) = create_temp_db();
 
     // Put and then delete a value
-    db.put("key1", "value1").unwrap();
+    db.put("key1", &"value1".to_string()).unwrap();
     assert_eq!(db.get("key1").unwrap(), "value1");
 
     db.delete("key1").unwrap();
 
     // Verify the key is no longer accessible
     match db.get("key1") {
-        Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
-        Ok(_) => panic!("Expected error after deletion"),
+        Err(Error::NotFound) => (),
+        _ => panic!("Expected NotFound error after deletion"),
     }
 }
 
@@ -68,14 +68,14 @@ fn test_persistence() {
 
     // Write data
     {
-        let mut db = Database::open(file_path.as_str()).unwrap();
-        db.put("key1", "value1").unwrap();
+        let mut db: Database<String> = Database::open(file_path.as_str()).unwrap();
+        db.put("key1", &"value1".to_string()).unwrap();
         db.close().unwrap();
     }
 
     // Read data from a new instance
     {
-        let db = Database::open(file_path.as_str()).unwrap();
+        let db: Database<String> = Database::open(file_path.as_str()).unwrap();
         assert_eq!(db.get("key1").unwrap(), "value1");
     }
 }
@@ -84,11 +84,11 @@ fn test_persistence() {
 fn test_update_existing_key() {
     let (mut db, _temp_file) = create_temp_db();
 
-    db.put("key1", "value1").unwrap();
+    db.put("key1", &"value1".to_string()).unwrap();
     assert_eq!(db.get("key1").unwrap(), "value1");
 
     // Update the value
-    db.put("key1", "new_value").unwrap();
+    db.put("key1", &"new_value".to_string()).unwrap();
     assert_eq!(db.get("key1").unwrap(), "new_value");
 }
 